@@ -28,6 +28,8 @@
 //!
 //! delegate_kde_appmenu!(State);
 //! ```
+use std::cell::RefCell;
+
 use wayland_protocols_plasma::appmenu::server::org_kde_kwin_appmenu::{
     OrgKdeKwinAppmenu, Request as AppmenuRequest,
 };
@@ -38,6 +40,8 @@ use wayland_server::backend::GlobalId;
 use wayland_server::protocol::wl_surface::WlSurface;
 use wayland_server::{Client, DataInit, Dispatch, DisplayHandle, GlobalDispatch, New};
 
+use crate::wayland::compositor::{add_destruction_hook, with_states};
+
 /// KDE appmenu handler.
 pub trait KdeAppMenuHandler {
     /// Return the KDE appmenu state.
@@ -116,6 +120,64 @@ impl KdeAppMenuState {
     pub fn global(&self) -> GlobalId {
         self.kde_appmenu_manager.clone()
     }
+
+    /// Returns the last `(service_name, object_path)` set for `surface`'s appmenu, if any.
+    ///
+    /// This lets a menu-bar widget poll for the currently focused window's global menu without
+    /// having to maintain its own side table keyed by surface.
+    pub fn address_for(surface: &WlSurface) -> Option<(String, String)> {
+        with_appmenu(surface, Clone::clone)
+    }
+}
+
+/// Per-surface bookkeeping backing [`KdeAppMenuState::address_for`].
+#[derive(Default)]
+struct AppmenuSurfaceState {
+    address: Option<(String, String)>,
+    hook_installed: bool,
+}
+
+fn with_appmenu_surface_state<F, T>(surface: &WlSurface, f: F) -> T
+where
+    F: FnOnce(&mut AppmenuSurfaceState) -> T,
+{
+    with_states(surface, |states| {
+        let cell = states
+            .data_map
+            .get_or_insert(RefCell::<AppmenuSurfaceState>::default);
+        f(&mut cell.borrow_mut())
+    })
+}
+
+/// Reads the last appmenu address set for `surface`, if any.
+pub fn with_appmenu<F, T>(surface: &WlSurface, f: F) -> T
+where
+    F: FnOnce(&Option<(String, String)>) -> T,
+{
+    with_appmenu_surface_state(surface, |state| f(&state.address))
+}
+
+fn set_appmenu_address(surface: &WlSurface, address: Option<(String, String)>) {
+    with_appmenu_surface_state(surface, |state| state.address = address);
+}
+
+/// Installs the destruction hook that clears the cached appmenu address, if one hasn't already
+/// been installed for this surface.
+///
+/// Guards against installing a redundant hook each time a window cycles through
+/// create/release, which is normal when an app's DBus menu service restarts.
+fn ensure_appmenu_destruction_hook(surface: &WlSurface) {
+    let needs_hook = with_appmenu_surface_state(surface, |state| {
+        let needed = !state.hook_installed;
+        state.hook_installed = true;
+        needed
+    });
+
+    if needs_hook {
+        add_destruction_hook(surface, |_data, surface| {
+            set_appmenu_address(surface, None);
+        });
+    }
 }
 
 #[allow(missing_docs)] // TODO
@@ -182,6 +244,9 @@ where
         };
 
         let appmenu = data_init.init(id, surface.clone());
+
+        ensure_appmenu_destruction_hook(&surface);
+
         state.new_appmenu(&surface, &appmenu);
     }
 }
@@ -204,9 +269,11 @@ where
                 service_name,
                 object_path,
             } => {
+                set_appmenu_address(surface, Some((service_name.clone(), object_path.clone())));
                 state.set_address(surface, appmenu, service_name, object_path);
             }
             AppmenuRequest::Release => {
+                set_appmenu_address(surface, None);
                 state.release(appmenu, surface);
             }
             _ => unreachable!(),
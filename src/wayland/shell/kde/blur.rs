@@ -30,12 +30,17 @@
 //! delegate_kde_blur!(State);
 //! ```
 
+use std::cell::RefCell;
+
 use wayland_protocols_plasma::blur::server::{
     org_kde_kwin_blur::OrgKdeKwinBlur,
     org_kde_kwin_blur_manager::OrgKdeKwinBlurManager,
 };
 use wayland_server::protocol::{wl_region::WlRegion, wl_surface::WlSurface};
-use wayland_server::{backend::GlobalId, Client, Dispatch, DisplayHandle, GlobalDispatch};
+use wayland_server::{backend::GlobalId, Client, Dispatch, DisplayHandle, GlobalDispatch, Resource};
+
+use crate::utils::{Logical, Rectangle};
+use crate::wayland::compositor::{self, with_states};
 
 /// KDE blur handler.
 pub trait KdeBlurHandler {
@@ -63,6 +68,139 @@ pub trait KdeBlurHandler {
     fn unset(&mut self, _surface: &WlSurface) {}
 }
 
+/// A set of rectangles describing a blur region, in surface-local logical coordinates.
+pub type RectangleSet = Vec<Rectangle<i32, Logical>>;
+
+/// Double-buffered blur state attached to a surface.
+///
+/// The `region`/`enabled` values here are only ever updated in lock-step with the surface's own
+/// `wl_surface.commit`, so render code reading this through [`with_blur_state`] always sees a
+/// blur region that is consistent with the buffer it is compositing.
+#[derive(Debug, Default, Clone)]
+pub struct BlurStateAttributes {
+    /// The region that should be blurred, or `None` to blur the whole surface.
+    pub region: Option<RectangleSet>,
+    /// Whether blur is currently requested for this surface.
+    pub enabled: bool,
+}
+
+/// Per-surface bookkeeping backing the double-buffered [`BlurStateAttributes`].
+///
+/// `region` is kept as the raw [`WlRegion`] until the blur object's own `commit` request, at
+/// which point it is resolved into a [`RectangleSet`] and staged in `pending`. A surface
+/// pre-commit hook then promotes `pending` into `current`.
+#[derive(Default)]
+struct BlurSurfaceState {
+    current: BlurStateAttributes,
+    pending: BlurStateAttributes,
+    pending_region: Option<WlRegion>,
+    hook_installed: bool,
+}
+
+/// Resolves a `wl_region` into the flat list of rectangles that make up the blur area.
+///
+/// This only keeps the region's `RectangleKind::Add` rectangles; it does not subtract out any
+/// `RectangleKind::Subtract` rectangles a client may have carved out (e.g. to exclude a video
+/// overlay from the blur), so a region built from add-then-subtract will currently overstate the
+/// blurred area by the subtracted rectangles.
+fn resolve_region(region: &WlRegion) -> Option<RectangleSet> {
+    if !region.is_alive() {
+        return None;
+    }
+
+    let attributes = compositor::get_region_attributes(region);
+    Some(
+        attributes
+            .rects
+            .into_iter()
+            .filter(|(kind, _)| *kind == compositor::RectangleKind::Add)
+            .map(|(_, rect)| rect)
+            .collect(),
+    )
+}
+
+fn with_blur_surface_state<F, T>(surface: &WlSurface, f: F) -> T
+where
+    F: FnOnce(&mut BlurSurfaceState) -> T,
+{
+    with_states(surface, |states| {
+        let cell = states
+            .data_map
+            .get_or_insert(RefCell::<BlurSurfaceState>::default);
+        f(&mut cell.borrow_mut())
+    })
+}
+
+/// Installs the pre-commit hook that promotes the staged blur state into the committed state,
+/// if one hasn't already been installed for this surface.
+pub(crate) fn ensure_blur_pre_commit_hook<D: 'static>(surface: &WlSurface) {
+    let needs_hook = with_blur_surface_state(surface, |state| {
+        let needed = !state.hook_installed;
+        state.hook_installed = true;
+        needed
+    });
+
+    if needs_hook {
+        compositor::add_pre_commit_hook::<D, _>(surface, |_state, _dh, surface| {
+            with_blur_surface_state(surface, |state| {
+                state.current = state.pending.clone();
+            });
+        });
+    }
+}
+
+/// Stages the blur region set by `org_kde_kwin_blur.set_region` for the next blur commit.
+///
+/// The region is kept as a handle rather than resolved immediately, so that a region destroyed
+/// before the blur object is committed is simply dropped instead of producing stale rectangles.
+pub(crate) fn stage_set_region(surface: &WlSurface, region: Option<&WlRegion>) {
+    with_blur_surface_state(surface, |state| {
+        state.pending_region = region.cloned();
+    });
+}
+
+/// Resolves the currently staged region and commits the pending blur state for `surface`.
+///
+/// This corresponds to `org_kde_kwin_blur.commit`; the result only becomes visible through
+/// [`with_blur_state`] once the surface itself is committed.
+pub(crate) fn stage_commit(surface: &WlSurface) {
+    with_blur_surface_state(surface, |state| {
+        state.pending.region = state.pending_region.as_ref().and_then(resolve_region);
+        state.pending.enabled = true;
+    });
+}
+
+/// Stages the removal of blur for `surface`, as requested by `org_kde_kwin_blur_manager.unset`.
+pub(crate) fn stage_unset(surface: &WlSurface) {
+    with_blur_surface_state(surface, |state| {
+        state.pending_region = None;
+        state.pending.region = None;
+        state.pending.enabled = false;
+    });
+}
+
+/// Clears the staged (pending) blur state for `surface`, e.g. when its blur object is released.
+///
+/// The committed state read through [`with_blur_state`] is cleared on the surface's next commit.
+pub(crate) fn clear_blur_state(surface: &WlSurface) {
+    with_blur_surface_state(surface, |state| {
+        state.pending_region = None;
+        state.pending = BlurStateAttributes::default();
+    });
+}
+
+/// Reads the currently committed blur state for `surface`.
+///
+/// Intended for use by render code that needs to know the blur region for the frame it is about
+/// to composite; the returned state is always consistent with the most recently committed
+/// buffer.
+pub fn with_blur_state<F, T>(surface: &WlSurface, f: F) -> T
+where
+    F: FnOnce(&BlurStateAttributes) -> T,
+{
+    with_blur_surface_state(surface, |state| f(&state.current))
+}
+
 /// KDE blur state.
 #[derive(Debug)]
 pub struct KdeBlurState {
@@ -0,0 +1,216 @@
+//! KDE server-side decoration palette protocol
+//!
+//! This interface allows a client to tell the compositor which color-scheme/palette file it
+//! would like the compositor to use when drawing that client's server-side decorations.
+//!
+//! ```
+//! extern crate wayland_server;
+//! extern crate smithay;
+//!
+//! use smithay::delegate_kde_decoration_palette;
+//! use smithay::wayland::shell::kde::decoration_palette::{
+//!     KdeDecorationPaletteHandler, KdeDecorationPaletteState,
+//! };
+//!
+//! # struct State { kde_decoration_palette_state: KdeDecorationPaletteState };
+//! # let mut display = wayland_server::Display::<State>::new().unwrap();
+//!
+//! // Create the new KdeDecorationPaletteState.
+//! let state = KdeDecorationPaletteState::new::<State>(&display.handle());
+//!
+//! // Insert KdeDecorationPaletteState into your compositor state.
+//! // …
+//!
+//! // Implement KDE decoration palette handlers.
+//! impl KdeDecorationPaletteHandler for State {
+//!     fn kde_decoration_palette_state(&self) -> &KdeDecorationPaletteState {
+//!         &self.kde_decoration_palette_state
+//!     }
+//! }
+//!
+//! delegate_kde_decoration_palette!(State);
+//! ```
+use wayland_protocols_plasma::server_decoration_palette::server::org_kde_kwin_server_decoration_palette::{
+    OrgKdeKwinServerDecorationPalette, Request as PaletteRequest,
+};
+use wayland_protocols_plasma::server_decoration_palette::server::org_kde_kwin_server_decoration_palette_manager::{
+    OrgKdeKwinServerDecorationPaletteManager, Request as ManagerRequest,
+};
+use wayland_server::backend::GlobalId;
+use wayland_server::protocol::wl_surface::WlSurface;
+use wayland_server::{Client, DataInit, Dispatch, DisplayHandle, GlobalDispatch, New};
+
+/// KDE decoration palette handler.
+pub trait KdeDecorationPaletteHandler {
+    /// Return the KDE decoration palette state.
+    fn kde_decoration_palette_state(&self) -> &KdeDecorationPaletteState;
+
+    /// Handle new decoration palette object creation.
+    ///
+    /// Called whenever a new decoration palette object is created, usually this happens when a
+    /// new window is opened.
+    fn new_palette(&mut self, _surface: &WlSurface, _palette: &OrgKdeKwinServerDecorationPalette) {}
+
+    /// Handle the palette name being set.
+    ///
+    /// Called when a client provides the name of the palette file the compositor should use
+    /// when drawing the decoration for this surface.
+    fn set_palette(
+        &mut self,
+        _surface: &WlSurface,
+        _palette: &OrgKdeKwinServerDecorationPalette,
+        _name: String,
+    ) {
+    }
+
+    /// Handle decoration palette object removal for a surface.
+    fn release(&mut self, _palette: &OrgKdeKwinServerDecorationPalette, _surface: &WlSurface) {}
+}
+
+/// KDE decoration palette state.
+#[derive(Debug)]
+pub struct KdeDecorationPaletteState {
+    kde_decoration_palette_manager: GlobalId,
+}
+
+/// Data associated with a KdeDecorationPaletteManager global.
+#[allow(missing_debug_implementations)]
+pub struct KdeDecorationPaletteManagerGlobalData {
+    pub(crate) filter: Box<dyn for<'c> Fn(&'c Client) -> bool + Send + Sync>,
+}
+
+impl KdeDecorationPaletteState {
+    /// Create a new KDE decoration palette global.
+    pub fn new<D>(display: &DisplayHandle) -> Self
+    where
+        D: GlobalDispatch<OrgKdeKwinServerDecorationPaletteManager, KdeDecorationPaletteManagerGlobalData>
+            + Dispatch<OrgKdeKwinServerDecorationPaletteManager, ()>
+            + Dispatch<OrgKdeKwinServerDecorationPalette, WlSurface>
+            + KdeDecorationPaletteHandler
+            + 'static,
+    {
+        Self::new_with_filter::<D, _>(display, |_| true)
+    }
+
+    /// Create a new KDE decoration palette global with a filter.
+    ///
+    /// Filters can be used to limit visibility of a global to certain clients.
+    pub fn new_with_filter<D, F>(display: &DisplayHandle, filter: F) -> Self
+    where
+        D: GlobalDispatch<OrgKdeKwinServerDecorationPaletteManager, KdeDecorationPaletteManagerGlobalData>
+            + Dispatch<OrgKdeKwinServerDecorationPaletteManager, ()>
+            + Dispatch<OrgKdeKwinServerDecorationPalette, WlSurface>
+            + KdeDecorationPaletteHandler
+            + 'static,
+        F: for<'c> Fn(&'c Client) -> bool + Send + Sync + 'static,
+    {
+        let data = KdeDecorationPaletteManagerGlobalData {
+            filter: Box::new(filter),
+        };
+        let kde_decoration_palette_manager =
+            display.create_global::<D, OrgKdeKwinServerDecorationPaletteManager, _>(1, data);
+
+        Self {
+            kde_decoration_palette_manager,
+        }
+    }
+
+    /// Returns the id of the [`OrgKdeKwinServerDecorationPaletteManager`] global.
+    pub fn global(&self) -> GlobalId {
+        self.kde_decoration_palette_manager.clone()
+    }
+}
+
+#[allow(missing_docs)] // TODO
+#[macro_export]
+macro_rules! delegate_kde_decoration_palette {
+    ($(@<$( $lt:tt $( : $clt:tt $(+ $dlt:tt )* )? ),+>)? $ty: ty) => {
+        $crate::reexports::wayland_server::delegate_global_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            $crate::reexports::wayland_protocols_plasma::server_decoration_palette::server::org_kde_kwin_server_decoration_palette_manager::OrgKdeKwinServerDecorationPaletteManager: $crate::wayland::shell::kde::decoration_palette::KdeDecorationPaletteManagerGlobalData
+        ] => $crate::wayland::shell::kde::decoration_palette::KdeDecorationPaletteState);
+
+        $crate::reexports::wayland_server::delegate_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            $crate::reexports::wayland_protocols_plasma::server_decoration_palette::server::org_kde_kwin_server_decoration_palette_manager::OrgKdeKwinServerDecorationPaletteManager: ()
+        ] => $crate::wayland::shell::kde::decoration_palette::KdeDecorationPaletteState);
+
+        $crate::reexports::wayland_server::delegate_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            $crate::reexports::wayland_protocols_plasma::server_decoration_palette::server::org_kde_kwin_server_decoration_palette::OrgKdeKwinServerDecorationPalette: $crate::reexports::wayland_server::protocol::wl_surface::WlSurface
+        ] => $crate::wayland::shell::kde::decoration_palette::KdeDecorationPaletteState);
+    };
+}
+
+impl<D> GlobalDispatch<OrgKdeKwinServerDecorationPaletteManager, KdeDecorationPaletteManagerGlobalData, D>
+    for KdeDecorationPaletteState
+where
+    D: GlobalDispatch<OrgKdeKwinServerDecorationPaletteManager, KdeDecorationPaletteManagerGlobalData>
+        + Dispatch<OrgKdeKwinServerDecorationPaletteManager, ()>
+        + Dispatch<OrgKdeKwinServerDecorationPalette, WlSurface>
+        + KdeDecorationPaletteHandler
+        + 'static,
+{
+    fn bind(
+        _state: &mut D,
+        _dh: &DisplayHandle,
+        _client: &Client,
+        resource: New<OrgKdeKwinServerDecorationPaletteManager>,
+        _global_data: &KdeDecorationPaletteManagerGlobalData,
+        data_init: &mut DataInit<'_, D>,
+    ) {
+        data_init.init(resource, ());
+    }
+
+    fn can_view(client: Client, global_data: &KdeDecorationPaletteManagerGlobalData) -> bool {
+        (global_data.filter)(&client)
+    }
+}
+
+impl<D> Dispatch<OrgKdeKwinServerDecorationPaletteManager, (), D> for KdeDecorationPaletteState
+where
+    D: Dispatch<OrgKdeKwinServerDecorationPaletteManager, ()>
+        + Dispatch<OrgKdeKwinServerDecorationPalette, WlSurface>
+        + KdeDecorationPaletteHandler
+        + 'static,
+{
+    fn request(
+        state: &mut D,
+        _client: &Client,
+        _manager: &OrgKdeKwinServerDecorationPaletteManager,
+        request: ManagerRequest,
+        _data: &(),
+        _dh: &DisplayHandle,
+        data_init: &mut DataInit<'_, D>,
+    ) {
+        let (id, surface) = match request {
+            ManagerRequest::Create { id, surface } => (id, surface),
+            _ => unreachable!(),
+        };
+
+        let palette = data_init.init(id, surface.clone());
+        state.new_palette(&surface, &palette);
+    }
+}
+
+impl<D> Dispatch<OrgKdeKwinServerDecorationPalette, WlSurface, D> for KdeDecorationPaletteState
+where
+    D: Dispatch<OrgKdeKwinServerDecorationPalette, WlSurface> + KdeDecorationPaletteHandler + 'static,
+{
+    fn request(
+        state: &mut D,
+        _client: &Client,
+        palette: &OrgKdeKwinServerDecorationPalette,
+        request: PaletteRequest,
+        surface: &WlSurface,
+        _dh: &DisplayHandle,
+        _data_init: &mut DataInit<'_, D>,
+    ) {
+        match request {
+            PaletteRequest::SetPalette { palette: name } => {
+                state.set_palette(surface, palette, name);
+            }
+            PaletteRequest::Release => {
+                state.release(palette, surface);
+            }
+            _ => unreachable!(),
+        }
+    }
+}
@@ -74,9 +74,18 @@ where
         );
 
         match request {
-            Request::Commit => state.commit(surface, blur),
-            Request::SetRegion { region } => state.set_region(surface, blur, region.as_ref()),
-            Request::Release => state.release(blur, surface),
+            Request::Commit => {
+                crate::wayland::shell::kde::blur::stage_commit(surface);
+                state.commit(surface, blur);
+            }
+            Request::SetRegion { region } => {
+                crate::wayland::shell::kde::blur::stage_set_region(surface, region.as_ref());
+                state.set_region(surface, blur, region.as_ref());
+            }
+            Request::Release => {
+                crate::wayland::shell::kde::blur::clear_blur_state(surface);
+                state.release(blur, surface);
+            }
             _ => unreachable!(),
         }
     }
@@ -103,11 +112,13 @@ where
                 let blur = data_init.init(id, surface);
 
                 let surface = blur.data().unwrap();
+                crate::wayland::shell::kde::blur::ensure_blur_pre_commit_hook::<D>(surface);
                 state.new_blur(surface, &blur);
 
                 trace!(surface = ?surface, "Created blur object for surface");
             }
             ManagerRequest::Unset { surface } => {
+                crate::wayland::shell::kde::blur::stage_unset(&surface);
                 state.unset(&surface);
 
                 trace!(surface = ?surface, "Unset blur for surface");
@@ -0,0 +1,284 @@
+//! Plasma shell surface protocol
+//!
+//! This interface lets privileged clients such as panels, desktop backgrounds and OSDs position
+//! their surfaces explicitly and declare a special role so the compositor can honor panel
+//! struts and stacking.
+//!
+//! ```
+//! extern crate wayland_server;
+//! extern crate smithay;
+//!
+//! use smithay::delegate_kde_plasma_shell;
+//! use smithay::wayland::shell::kde::plasma_shell::{PlasmaShellHandler, PlasmaShellState};
+//!
+//! # struct State { plasma_shell_state: PlasmaShellState };
+//! # let mut display = wayland_server::Display::<State>::new().unwrap();
+//!
+//! // Create the new PlasmaShellState.
+//! let state = PlasmaShellState::new::<State>(&display.handle());
+//!
+//! // Insert PlasmaShellState into your compositor state.
+//! // …
+//!
+//! // Implement the Plasma shell handler.
+//! impl PlasmaShellHandler for State {
+//!     fn plasma_shell_state(&self) -> &PlasmaShellState {
+//!         &self.plasma_shell_state
+//!     }
+//! }
+//!
+//! delegate_kde_plasma_shell!(State);
+//! ```
+use std::cell::RefCell;
+
+use wayland_protocols_plasma::plasma_shell::server::org_kde_plasma_shell::{
+    OrgKdePlasmaShell, Request as ShellRequest,
+};
+use wayland_protocols_plasma::plasma_shell::server::org_kde_plasma_surface::{
+    OrgKdePlasmaSurface, PanelBehavior, Request as SurfaceRequest, Role,
+};
+use wayland_server::backend::GlobalId;
+use wayland_server::protocol::wl_output::WlOutput;
+use wayland_server::protocol::wl_surface::WlSurface;
+use wayland_server::{Client, DataInit, Dispatch, DisplayHandle, GlobalDispatch, New};
+
+use crate::wayland::compositor::with_states;
+
+/// Handler for the Plasma shell surface protocol.
+pub trait PlasmaShellHandler {
+    /// Return the Plasma shell state.
+    fn plasma_shell_state(&self) -> &PlasmaShellState;
+
+    /// Handle new plasma surface object creation.
+    ///
+    /// Called whenever a client turns a `wl_surface` into a plasma surface.
+    fn new_surface(&mut self, _surface: &WlSurface, _plasma_surface: &OrgKdePlasmaSurface) {}
+
+    /// Handle a client requesting auto-hide for a panel.
+    fn panel_auto_hide_hide(&mut self, _surface: &WlSurface, _plasma_surface: &OrgKdePlasmaSurface) {}
+
+    /// Handle a client cancelling auto-hide for a panel.
+    fn panel_auto_hide_show(&mut self, _surface: &WlSurface, _plasma_surface: &OrgKdePlasmaSurface) {}
+}
+
+/// The role a plasma surface declares for itself.
+///
+/// Mirrors [`Role`], re-exported here so compositors don't need to depend on the protocol crate
+/// directly.
+pub type PlasmaSurfaceRole = Role;
+
+/// Cached per-surface state maintained by the Plasma shell protocol.
+///
+/// Readable through [`with_plasma_surface_state`] so the compositor can honor panel struts and
+/// stacking without having to track this itself.
+#[derive(Debug, Default, Clone)]
+pub struct PlasmaSurfaceAttributes {
+    /// The role this surface was given via `set_role`.
+    pub role: Option<PlasmaSurfaceRole>,
+    /// The explicit position set via `set_position`, in surface-local logical coordinates.
+    pub position: Option<(i32, i32)>,
+    /// The output this surface was pinned to via `set_output`.
+    pub output: Option<WlOutput>,
+    /// The panel behavior set via `set_panel_behavior`; stored whatever role the surface has.
+    pub panel_behavior: Option<PanelBehavior>,
+    /// Whether the client asked to be hidden from the taskbar.
+    pub skip_taskbar: bool,
+    /// Whether the client asked to be hidden from the window switcher.
+    pub skip_switcher: bool,
+}
+
+/// Plasma shell state.
+#[derive(Debug)]
+pub struct PlasmaShellState {
+    plasma_shell: GlobalId,
+}
+
+/// Data associated with a PlasmaShell global.
+#[allow(missing_debug_implementations)]
+pub struct PlasmaShellGlobalData {
+    pub(crate) filter: Box<dyn for<'c> Fn(&'c Client) -> bool + Send + Sync>,
+}
+
+impl PlasmaShellState {
+    /// Create a new Plasma shell global.
+    pub fn new<D>(display: &DisplayHandle) -> Self
+    where
+        D: GlobalDispatch<OrgKdePlasmaShell, PlasmaShellGlobalData>
+            + Dispatch<OrgKdePlasmaShell, ()>
+            + Dispatch<OrgKdePlasmaSurface, WlSurface>
+            + PlasmaShellHandler
+            + 'static,
+    {
+        Self::new_with_filter::<D, _>(display, |_| true)
+    }
+
+    /// Create a new Plasma shell global with a filter.
+    ///
+    /// Filters can be used to limit visibility of a global to certain clients.
+    pub fn new_with_filter<D, F>(display: &DisplayHandle, filter: F) -> Self
+    where
+        D: GlobalDispatch<OrgKdePlasmaShell, PlasmaShellGlobalData>
+            + Dispatch<OrgKdePlasmaShell, ()>
+            + Dispatch<OrgKdePlasmaSurface, WlSurface>
+            + PlasmaShellHandler
+            + 'static,
+        F: for<'c> Fn(&'c Client) -> bool + Send + Sync + 'static,
+    {
+        let data = PlasmaShellGlobalData {
+            filter: Box::new(filter),
+        };
+        let plasma_shell = display.create_global::<D, OrgKdePlasmaShell, _>(6, data);
+
+        Self { plasma_shell }
+    }
+
+    /// Returns the id of the [`OrgKdePlasmaShell`] global.
+    pub fn global(&self) -> GlobalId {
+        self.plasma_shell.clone()
+    }
+}
+
+/// Reads the cached Plasma shell state for `surface`.
+pub fn with_plasma_surface_state<F, T>(surface: &WlSurface, f: F) -> T
+where
+    F: FnOnce(&PlasmaSurfaceAttributes) -> T,
+{
+    with_states(surface, |states| {
+        let cell = states
+            .data_map
+            .get_or_insert(RefCell::<PlasmaSurfaceAttributes>::default);
+        f(&cell.borrow())
+    })
+}
+
+fn update_plasma_surface_state<F>(surface: &WlSurface, f: F)
+where
+    F: FnOnce(&mut PlasmaSurfaceAttributes),
+{
+    with_states(surface, |states| {
+        let cell = states
+            .data_map
+            .get_or_insert(RefCell::<PlasmaSurfaceAttributes>::default);
+        f(&mut cell.borrow_mut())
+    })
+}
+
+#[allow(missing_docs)] // TODO
+#[macro_export]
+macro_rules! delegate_kde_plasma_shell {
+    ($(@<$( $lt:tt $( : $clt:tt $(+ $dlt:tt )* )? ),+>)? $ty: ty) => {
+        $crate::reexports::wayland_server::delegate_global_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            $crate::reexports::wayland_protocols_plasma::plasma_shell::server::org_kde_plasma_shell::OrgKdePlasmaShell: $crate::wayland::shell::kde::plasma_shell::PlasmaShellGlobalData
+        ] => $crate::wayland::shell::kde::plasma_shell::PlasmaShellState);
+
+        $crate::reexports::wayland_server::delegate_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            $crate::reexports::wayland_protocols_plasma::plasma_shell::server::org_kde_plasma_shell::OrgKdePlasmaShell: ()
+        ] => $crate::wayland::shell::kde::plasma_shell::PlasmaShellState);
+
+        $crate::reexports::wayland_server::delegate_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            $crate::reexports::wayland_protocols_plasma::plasma_shell::server::org_kde_plasma_surface::OrgKdePlasmaSurface: $crate::reexports::wayland_server::protocol::wl_surface::WlSurface
+        ] => $crate::wayland::shell::kde::plasma_shell::PlasmaShellState);
+    };
+}
+
+impl<D> GlobalDispatch<OrgKdePlasmaShell, PlasmaShellGlobalData, D> for PlasmaShellState
+where
+    D: GlobalDispatch<OrgKdePlasmaShell, PlasmaShellGlobalData>
+        + Dispatch<OrgKdePlasmaShell, ()>
+        + Dispatch<OrgKdePlasmaSurface, WlSurface>
+        + PlasmaShellHandler
+        + 'static,
+{
+    fn bind(
+        _state: &mut D,
+        _dh: &DisplayHandle,
+        _client: &Client,
+        resource: New<OrgKdePlasmaShell>,
+        _global_data: &PlasmaShellGlobalData,
+        data_init: &mut DataInit<'_, D>,
+    ) {
+        data_init.init(resource, ());
+    }
+
+    fn can_view(client: Client, global_data: &PlasmaShellGlobalData) -> bool {
+        (global_data.filter)(&client)
+    }
+}
+
+impl<D> Dispatch<OrgKdePlasmaShell, (), D> for PlasmaShellState
+where
+    D: Dispatch<OrgKdePlasmaShell, ()>
+        + Dispatch<OrgKdePlasmaSurface, WlSurface>
+        + PlasmaShellHandler
+        + 'static,
+{
+    fn request(
+        state: &mut D,
+        _client: &Client,
+        _shell: &OrgKdePlasmaShell,
+        request: ShellRequest,
+        _data: &(),
+        _dh: &DisplayHandle,
+        data_init: &mut DataInit<'_, D>,
+    ) {
+        let (id, surface) = match request {
+            ShellRequest::GetSurface { id, surface } => (id, surface),
+            _ => unreachable!(),
+        };
+
+        let plasma_surface = data_init.init(id, surface.clone());
+        state.new_surface(&surface, &plasma_surface);
+    }
+}
+
+impl<D> Dispatch<OrgKdePlasmaSurface, WlSurface, D> for PlasmaShellState
+where
+    D: Dispatch<OrgKdePlasmaSurface, WlSurface> + PlasmaShellHandler + 'static,
+{
+    fn request(
+        state: &mut D,
+        _client: &Client,
+        plasma_surface: &OrgKdePlasmaSurface,
+        request: SurfaceRequest,
+        surface: &WlSurface,
+        _dh: &DisplayHandle,
+        _data_init: &mut DataInit<'_, D>,
+    ) {
+        match request {
+            SurfaceRequest::SetOutput { output } => {
+                update_plasma_surface_state(surface, |state| state.output = Some(output));
+            }
+            SurfaceRequest::SetPosition { x, y } => {
+                update_plasma_surface_state(surface, |state| state.position = Some((x, y)));
+            }
+            SurfaceRequest::SetRole { role } => {
+                update_plasma_surface_state(surface, |state| {
+                    state.role = role.into_result().ok();
+                });
+            }
+            SurfaceRequest::SetPanelBehavior { flag } => {
+                update_plasma_surface_state(surface, |state| {
+                    state.panel_behavior = flag.into_result().ok();
+                });
+            }
+            SurfaceRequest::SetSkipTaskbar { skip } => {
+                update_plasma_surface_state(surface, |state| state.skip_taskbar = skip);
+            }
+            SurfaceRequest::SetSkipSwitcher { skip } => {
+                update_plasma_surface_state(surface, |state| state.skip_switcher = skip);
+            }
+            SurfaceRequest::PanelAutoHideHide => {
+                state.panel_auto_hide_hide(surface, plasma_surface);
+            }
+            SurfaceRequest::PanelAutoHideShow => {
+                state.panel_auto_hide_show(surface, plasma_surface);
+            }
+            SurfaceRequest::Release => {
+                update_plasma_surface_state(surface, |state| {
+                    *state = PlasmaSurfaceAttributes::default();
+                });
+            }
+            _ => unreachable!(),
+        }
+    }
+}
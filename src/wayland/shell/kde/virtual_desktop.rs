@@ -0,0 +1,392 @@
+//! Plasma virtual desktop management protocol
+//!
+//! This interface lets KWin-style pagers and panels enumerate, switch, create and remove the
+//! compositor's virtual desktops.
+//!
+//! ```
+//! extern crate wayland_server;
+//! extern crate smithay;
+//!
+//! use smithay::delegate_kde_virtual_desktop;
+//! use smithay::wayland::shell::kde::virtual_desktop::{VirtualDesktopHandler, VirtualDesktopState};
+//!
+//! # struct State { virtual_desktop_state: VirtualDesktopState };
+//! # let mut display = wayland_server::Display::<State>::new().unwrap();
+//!
+//! // Create the new VirtualDesktopState.
+//! let state = VirtualDesktopState::new::<State>(&display.handle());
+//!
+//! // Insert VirtualDesktopState into your compositor state.
+//! // …
+//!
+//! // Implement the virtual desktop handler.
+//! impl VirtualDesktopHandler for State {
+//!     fn virtual_desktop_state(&self) -> &VirtualDesktopState {
+//!         &self.virtual_desktop_state
+//!     }
+//! }
+//!
+//! delegate_kde_virtual_desktop!(State);
+//! ```
+use std::sync::{Arc, Mutex};
+
+use wayland_protocols_plasma::virtual_desktop::server::org_kde_plasma_virtual_desktop::{
+    OrgKdePlasmaVirtualDesktop, Request as DesktopRequest,
+};
+use wayland_protocols_plasma::virtual_desktop::server::org_kde_plasma_virtual_desktop_management::{
+    OrgKdePlasmaVirtualDesktopManagement, Request as ManagerRequest,
+};
+use wayland_server::backend::GlobalId;
+use wayland_server::{Client, DataInit, Dispatch, DisplayHandle, GlobalDispatch, New, Resource};
+
+/// Handler for the Plasma virtual desktop management protocol.
+pub trait VirtualDesktopHandler {
+    /// Return the virtual desktop state.
+    fn virtual_desktop_state(&self) -> &VirtualDesktopState;
+
+    /// A client asked the compositor to create a new virtual desktop.
+    ///
+    /// The compositor should call [`VirtualDesktopState::add_desktop`] with a freshly allocated
+    /// id if it honors the request.
+    fn request_create_virtual_desktop(&mut self, _name: String, _position: u32) {}
+
+    /// A client asked the compositor to remove a virtual desktop.
+    ///
+    /// The compositor should call [`VirtualDesktopState::remove_desktop`] if it honors the
+    /// request.
+    fn request_remove_virtual_desktop(&mut self, _id: String) {}
+
+    /// A client asked to switch to a virtual desktop it already holds a handle for.
+    ///
+    /// The compositor should call [`VirtualDesktopState::set_active`] if it honors the request.
+    fn request_activate_virtual_desktop(&mut self, _id: String) {}
+}
+
+#[derive(Debug, Clone)]
+struct Desktop {
+    id: String,
+    name: String,
+    position: u32,
+    active: bool,
+}
+
+#[derive(Debug, Default)]
+struct VirtualDesktopManagerInner {
+    desktops: Vec<Desktop>,
+    rows: u32,
+    managers: Vec<OrgKdePlasmaVirtualDesktopManagement>,
+    desktop_objects: Vec<OrgKdePlasmaVirtualDesktop>,
+}
+
+impl VirtualDesktopManagerInner {
+    fn desktop(&self, id: &str) -> Option<&Desktop> {
+        self.desktops.iter().find(|desktop| desktop.id == id)
+    }
+
+    fn desktop_objects_for(&self, id: &str) -> impl Iterator<Item = &OrgKdePlasmaVirtualDesktop> {
+        self.desktop_objects
+            .iter()
+            .filter(move |object| object.data::<String>().map(String::as_str) == Some(id))
+    }
+
+    /// Keeps each desktop's stored `position` in sync with its index, so that `bind` always
+    /// replays the layout clients actually observed after an insert/remove in the middle of the
+    /// list.
+    fn renumber_desktops(&mut self) {
+        for (index, desktop) in self.desktops.iter_mut().enumerate() {
+            desktop.position = index as u32;
+        }
+    }
+}
+
+/// Plasma virtual desktop management state.
+///
+/// Owns the ordered list of virtual desktops and broadcasts changes to every bound
+/// `org_kde_plasma_virtual_desktop_management` global.
+#[derive(Debug)]
+pub struct VirtualDesktopState {
+    virtual_desktop_manager: GlobalId,
+    inner: Arc<Mutex<VirtualDesktopManagerInner>>,
+}
+
+/// Data associated with a VirtualDesktopManagement global.
+#[allow(missing_debug_implementations)]
+pub struct VirtualDesktopManagerGlobalData {
+    pub(crate) filter: Box<dyn for<'c> Fn(&'c Client) -> bool + Send + Sync>,
+}
+
+impl VirtualDesktopState {
+    /// Create a new Plasma virtual desktop management global.
+    pub fn new<D>(display: &DisplayHandle) -> Self
+    where
+        D: GlobalDispatch<OrgKdePlasmaVirtualDesktopManagement, VirtualDesktopManagerGlobalData>
+            + Dispatch<OrgKdePlasmaVirtualDesktopManagement, ()>
+            + Dispatch<OrgKdePlasmaVirtualDesktop, String>
+            + VirtualDesktopHandler
+            + 'static,
+    {
+        Self::new_with_filter::<D, _>(display, |_| true)
+    }
+
+    /// Create a new Plasma virtual desktop management global with a filter.
+    ///
+    /// Filters can be used to limit visibility of a global to certain clients.
+    pub fn new_with_filter<D, F>(display: &DisplayHandle, filter: F) -> Self
+    where
+        D: GlobalDispatch<OrgKdePlasmaVirtualDesktopManagement, VirtualDesktopManagerGlobalData>
+            + Dispatch<OrgKdePlasmaVirtualDesktopManagement, ()>
+            + Dispatch<OrgKdePlasmaVirtualDesktop, String>
+            + VirtualDesktopHandler
+            + 'static,
+        F: for<'c> Fn(&'c Client) -> bool + Send + Sync + 'static,
+    {
+        let data = VirtualDesktopManagerGlobalData {
+            filter: Box::new(filter),
+        };
+        let virtual_desktop_manager =
+            display.create_global::<D, OrgKdePlasmaVirtualDesktopManagement, _>(1, data);
+
+        Self {
+            virtual_desktop_manager,
+            inner: Arc::new(Mutex::new(VirtualDesktopManagerInner::default())),
+        }
+    }
+
+    /// Returns the id of the [`OrgKdePlasmaVirtualDesktopManagement`] global.
+    pub fn global(&self) -> GlobalId {
+        self.virtual_desktop_manager.clone()
+    }
+
+    /// Adds a new virtual desktop at `position`, broadcasting `desktop_created` to every bound
+    /// manager.
+    pub fn add_desktop(&mut self, id: impl Into<String>, name: impl Into<String>, position: u32) {
+        let id = id.into();
+        let name = name.into();
+        let mut inner = self.inner.lock().unwrap();
+
+        let position = (position as usize).min(inner.desktops.len());
+        inner.desktops.insert(
+            position,
+            Desktop {
+                id: id.clone(),
+                name,
+                position: position as u32,
+                active: false,
+            },
+        );
+        inner.renumber_desktops();
+
+        for manager in inner.managers.clone() {
+            manager.desktop_created(id.clone(), position as u32);
+            manager.done();
+        }
+    }
+
+    /// Removes the virtual desktop identified by `id`, sending `removed` to every per-desktop
+    /// object still bound to it and broadcasting `desktop_removed` to every bound manager.
+    pub fn remove_desktop(&mut self, id: &str) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.desktops.retain(|desktop| desktop.id != id);
+        inner.renumber_desktops();
+
+        let objects: Vec<_> = inner.desktop_objects_for(id).cloned().collect();
+        inner.desktop_objects.retain(|object| object.data::<String>().map(String::as_str) != Some(id));
+
+        for object in objects {
+            object.removed();
+        }
+
+        for manager in inner.managers.clone() {
+            manager.desktop_removed(id.to_string());
+            manager.done();
+        }
+    }
+
+    /// Marks `id` as the active desktop, deactivating the previously active one, and notifies
+    /// the per-desktop objects of each desktop whose active state changed.
+    pub fn set_active(&mut self, id: &str) {
+        let mut inner = self.inner.lock().unwrap();
+        let mut changed = Vec::new();
+
+        for desktop in &mut inner.desktops {
+            let active = desktop.id == id;
+            if desktop.active != active {
+                desktop.active = active;
+                changed.push(desktop.id.clone());
+            }
+        }
+
+        for changed_id in changed {
+            let active = inner.desktop(&changed_id).map(|desktop| desktop.active).unwrap_or(false);
+            for object in inner.desktop_objects_for(&changed_id).cloned().collect::<Vec<_>>() {
+                if active {
+                    object.activated();
+                } else {
+                    object.deactivated();
+                }
+                object.done();
+            }
+        }
+    }
+
+    /// Sets the number of rows used to lay out desktops, broadcasting `rows` to every bound
+    /// manager.
+    pub fn set_rows(&mut self, rows: u32) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.rows = rows;
+
+        for manager in inner.managers.clone() {
+            manager.rows(rows);
+        }
+    }
+}
+
+#[allow(missing_docs)] // TODO
+#[macro_export]
+macro_rules! delegate_kde_virtual_desktop {
+    ($(@<$( $lt:tt $( : $clt:tt $(+ $dlt:tt )* )? ),+>)? $ty: ty) => {
+        $crate::reexports::wayland_server::delegate_global_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            $crate::reexports::wayland_protocols_plasma::virtual_desktop::server::org_kde_plasma_virtual_desktop_management::OrgKdePlasmaVirtualDesktopManagement: $crate::wayland::shell::kde::virtual_desktop::VirtualDesktopManagerGlobalData
+        ] => $crate::wayland::shell::kde::virtual_desktop::VirtualDesktopState);
+
+        $crate::reexports::wayland_server::delegate_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            $crate::reexports::wayland_protocols_plasma::virtual_desktop::server::org_kde_plasma_virtual_desktop_management::OrgKdePlasmaVirtualDesktopManagement: ()
+        ] => $crate::wayland::shell::kde::virtual_desktop::VirtualDesktopState);
+
+        $crate::reexports::wayland_server::delegate_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            $crate::reexports::wayland_protocols_plasma::virtual_desktop::server::org_kde_plasma_virtual_desktop::OrgKdePlasmaVirtualDesktop: String
+        ] => $crate::wayland::shell::kde::virtual_desktop::VirtualDesktopState);
+    };
+}
+
+impl<D> GlobalDispatch<OrgKdePlasmaVirtualDesktopManagement, VirtualDesktopManagerGlobalData, D>
+    for VirtualDesktopState
+where
+    D: GlobalDispatch<OrgKdePlasmaVirtualDesktopManagement, VirtualDesktopManagerGlobalData>
+        + Dispatch<OrgKdePlasmaVirtualDesktopManagement, ()>
+        + Dispatch<OrgKdePlasmaVirtualDesktop, String>
+        + VirtualDesktopHandler
+        + 'static,
+{
+    fn bind(
+        state: &mut D,
+        _dh: &DisplayHandle,
+        _client: &Client,
+        resource: New<OrgKdePlasmaVirtualDesktopManagement>,
+        _global_data: &VirtualDesktopManagerGlobalData,
+        data_init: &mut DataInit<'_, D>,
+    ) {
+        let manager = data_init.init(resource, ());
+
+        let mut inner = state.virtual_desktop_state().inner.lock().unwrap();
+        for desktop in inner.desktops.clone() {
+            manager.desktop_created(desktop.id, desktop.position);
+        }
+        manager.done();
+        inner.managers.push(manager);
+    }
+
+    fn can_view(client: Client, global_data: &VirtualDesktopManagerGlobalData) -> bool {
+        (global_data.filter)(&client)
+    }
+}
+
+impl<D> Dispatch<OrgKdePlasmaVirtualDesktopManagement, (), D> for VirtualDesktopState
+where
+    D: Dispatch<OrgKdePlasmaVirtualDesktopManagement, ()>
+        + Dispatch<OrgKdePlasmaVirtualDesktop, String>
+        + VirtualDesktopHandler
+        + 'static,
+{
+    fn request(
+        state: &mut D,
+        _client: &Client,
+        _manager: &OrgKdePlasmaVirtualDesktopManagement,
+        request: ManagerRequest,
+        _data: &(),
+        _dh: &DisplayHandle,
+        data_init: &mut DataInit<'_, D>,
+    ) {
+        match request {
+            ManagerRequest::GetVirtualDesktop { id, desktop } => {
+                let object = data_init.init(desktop, id.clone());
+
+                let inner = state.virtual_desktop_state().inner.lock().unwrap();
+                if let Some(found) = inner.desktop(&id) {
+                    object.desktop_id(found.id.clone());
+                    object.name(found.name.clone());
+                    if found.active {
+                        object.activated();
+                    }
+                }
+                object.done();
+                drop(inner);
+
+                state
+                    .virtual_desktop_state()
+                    .inner
+                    .lock()
+                    .unwrap()
+                    .desktop_objects
+                    .push(object);
+            }
+            ManagerRequest::RequestCreateVirtualDesktop { name, position } => {
+                state.request_create_virtual_desktop(name, position);
+            }
+            ManagerRequest::RequestRemoveVirtualDesktop { id } => {
+                state.request_remove_virtual_desktop(id);
+            }
+            ManagerRequest::Destroy => {
+                let mut inner = state.virtual_desktop_state().inner.lock().unwrap();
+                inner.managers.retain(|manager| manager != _manager);
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    fn destroyed(
+        state: &mut D,
+        _client: wayland_server::backend::ClientId,
+        manager: &OrgKdePlasmaVirtualDesktopManagement,
+        _data: &(),
+    ) {
+        let mut inner = state.virtual_desktop_state().inner.lock().unwrap();
+        inner.managers.retain(|bound| bound != manager);
+    }
+}
+
+impl<D> Dispatch<OrgKdePlasmaVirtualDesktop, String, D> for VirtualDesktopState
+where
+    D: Dispatch<OrgKdePlasmaVirtualDesktop, String> + VirtualDesktopHandler + 'static,
+{
+    fn request(
+        state: &mut D,
+        _client: &Client,
+        desktop: &OrgKdePlasmaVirtualDesktop,
+        request: DesktopRequest,
+        _id: &String,
+        _dh: &DisplayHandle,
+        _data_init: &mut DataInit<'_, D>,
+    ) {
+        match request {
+            DesktopRequest::Release => {
+                let mut inner = state.virtual_desktop_state().inner.lock().unwrap();
+                inner.desktop_objects.retain(|object| object != desktop);
+            }
+            DesktopRequest::RequestActivate => {
+                state.request_activate_virtual_desktop(_id.clone());
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    fn destroyed(
+        state: &mut D,
+        _client: wayland_server::backend::ClientId,
+        desktop: &OrgKdePlasmaVirtualDesktop,
+        _data: &String,
+    ) {
+        let mut inner = state.virtual_desktop_state().inner.lock().unwrap();
+        inner.desktop_objects.retain(|bound| bound != desktop);
+    }
+}